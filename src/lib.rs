@@ -12,10 +12,131 @@
 //! Crate eztotp provides a easy-to-use Totp solution, [Totp]. See documentations of the
 //! struct for further information.
 
+#[cfg(feature = "qr")]
+use base64::Engine;
 use google_authenticator::GA_AUTH;
+use hmac::{Hmac, Mac};
 use rand::Rng;
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
 use std::time::{SystemTime, SystemTimeError, UNIX_EPOCH};
 
+/// Hash algorithm used to derive a TOTP code.
+///
+/// # Default
+///
+/// [Algorithm::Sha1], matching the original Google Authenticator behavior.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    fn as_str(self) -> &'static str {
+        match self {
+            Algorithm::Sha1 => "SHA1",
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Sha512 => "SHA512",
+        }
+    }
+}
+
+impl Default for Algorithm {
+    fn default() -> Self {
+        Algorithm::Sha1
+    }
+}
+
+/// Minimum secret size, in bytes, accepted by [Totp::with_secret] and [Totp::from_uri] (80
+/// bits, the size of the 16-character base32 secrets commonly issued by Google and others; 160
+/// bits, as [Totp::new] generates, is recommended for new secrets).
+const MIN_SECRET_BYTES: usize = 10;
+
+/// Maximum number of digits a code can have without overflowing `10u32.pow(digits)` in [hotp].
+const MAX_DIGITS: u32 = 9;
+
+fn default_digits() -> u32 {
+    6
+}
+
+fn default_step() -> u64 {
+    30
+}
+
+/// Decodes a base32 encoded shared secret into raw bytes.
+///
+/// Returns `None` if `secret` is not valid base32.
+fn decode_secret(secret: &str) -> Option<Vec<u8>> {
+    base32::decode(base32::Alphabet::RFC4648 { padding: false }, secret)
+}
+
+/// Computes the HOTP value (RFC 4226) for `counter`, using `secret` as the base32 encoded
+/// shared secret.
+///
+/// Returns `None` if `secret` is not valid base32.
+fn hotp(secret: &str, counter: u64, algorithm: Algorithm, digits: u32) -> Option<String> {
+    let key = decode_secret(secret)?;
+    let counter = counter.to_be_bytes();
+
+    let hash = match algorithm {
+        Algorithm::Sha1 => {
+            let mut mac = Hmac::<Sha1>::new_from_slice(&key).ok()?;
+            mac.update(&counter);
+            mac.finalize().into_bytes().to_vec()
+        }
+        Algorithm::Sha256 => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(&key).ok()?;
+            mac.update(&counter);
+            mac.finalize().into_bytes().to_vec()
+        }
+        Algorithm::Sha512 => {
+            let mut mac = Hmac::<Sha512>::new_from_slice(&key).ok()?;
+            mac.update(&counter);
+            mac.finalize().into_bytes().to_vec()
+        }
+    };
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let truncated = ((hash[offset] as u32 & 0x7f) << 24)
+        | ((hash[offset + 1] as u32) << 16)
+        | ((hash[offset + 2] as u32) << 8)
+        | (hash[offset + 3] as u32);
+    let modulo = 10u32.pow(digits);
+
+    Some(format!(
+        "{:0width$}",
+        truncated % modulo,
+        width = digits as usize
+    ))
+}
+
+/// Decodes a `application/x-www-form-urlencoded`-style percent-encoded string, as used in the
+/// query part of an `otpauth://` uri.
+fn url_decode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '+' => out.push(' '),
+            '%' => {
+                let hi = chars.next();
+                let lo = chars.next();
+                match (
+                    hi.and_then(|c| c.to_digit(16)),
+                    lo.and_then(|c| c.to_digit(16)),
+                ) {
+                    (Some(hi), Some(lo)) => out.push((hi as u8 * 16 + lo as u8) as char),
+                    _ => out.push('%'),
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
 /// A ready-to-use TOTP solution.
 ///
 /// It supports some features not directly related to Totp:
@@ -39,6 +160,12 @@ pub struct Totp {
     window: u16,
     reusable: bool,
     last_step: u64,
+    #[serde(default)]
+    algorithm: Algorithm,
+    #[serde(default = "default_digits")]
+    digits: u32,
+    #[serde(default = "default_step")]
+    step: u64,
 }
 
 impl Totp {
@@ -48,13 +175,14 @@ impl Totp {
     ///
     /// - Number of scratch codes: 8.
     /// - Code reusing: forbid.
-    /// - Delay time (window): 1 second, as suggested in RFC document.
+    /// - Skew window: 1 step, as suggested in RFC document.
+    /// - Hash algorithm: SHA1.
+    /// - Size of time frame: 30 seconds.
+    /// - Code length: 6 digits.
     ///
     /// Some parameters are hard-coded:
     ///
     /// - Secret length: 32 characters (160 bits).
-    /// - Size of time frame: 30 seconds.
-    /// - Code length: 6 digits.
     /// - Scratch code length: 8 digits.
     #[must_use]
     pub fn new() -> Self {
@@ -64,10 +192,69 @@ impl Totp {
             window: 1,
             reusable: false,
             last_step: 0,
+            algorithm: Algorithm::default(),
+            digits: default_digits(),
+            step: default_step(),
         }
         .with_scratch(8)
     }
 
+    /// Wraps an existing base32 encoded shared secret, instead of generating a fresh one.
+    ///
+    /// Useful when migrating accounts from another authenticator, or provisioning a secret
+    /// from an HSM/KDF — common sizes like 80-bit and 128-bit secrets are both accepted. Returns
+    /// [SecretError::InvalidBase32] if `secret` is not valid base32, or
+    /// [SecretError::InvalidLength] if it decodes to fewer than 80 bits (10 bytes).
+    ///
+    /// Otherwise behaves like [Totp::new]: 8 scratch codes, code reusing forbidden, 1 step skew
+    /// window, SHA1, 6 digits, 30-second steps.
+    pub fn with_secret(secret: impl Into<String>) -> Result<Self, SecretError> {
+        let secret = secret.into();
+        let key = decode_secret(&secret).ok_or(SecretError::InvalidBase32)?;
+        if key.len() < MIN_SECRET_BYTES {
+            return Err(SecretError::InvalidLength);
+        }
+
+        Ok(Totp {
+            secret,
+            scratch: vec![],
+            window: 1,
+            reusable: false,
+            last_step: 0,
+            algorithm: Algorithm::default(),
+            digits: default_digits(),
+            step: default_step(),
+        }
+        .with_scratch(8))
+    }
+
+    /// Set the hash algorithm used to derive codes.
+    #[must_use]
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Set the number of digits a code has.
+    ///
+    /// `digits` is clamped to `1..=9`: codes are computed mod `10.pow(digits)`, which would
+    /// overflow `u32` at 10 or more digits.
+    #[must_use]
+    pub fn with_digits(mut self, digits: u32) -> Self {
+        self.digits = digits.clamp(1, MAX_DIGITS);
+        self
+    }
+
+    /// Set the size of a time frame, in seconds.
+    ///
+    /// `step` is clamped to a minimum of `1`, since `0` would make a time frame meaningless
+    /// (and would panic on the division in [Totp::verify_code] / [Totp::generate_at]).
+    #[must_use]
+    pub fn with_step(mut self, step: u64) -> Self {
+        self.step = step.max(1);
+        self
+    }
+
     /// Regenerate scratch codes. The codes generated will be 8 digits.
     ///
     /// You may set `num` to `0` to completely disable scratch code.
@@ -82,9 +269,13 @@ impl Totp {
         self
     }
 
-    /// Set time window.
+    /// Set the skew window.
     ///
-    /// The `window` indicates number of seconds ago that a code may be generated.
+    /// `window` is the half-width, in time steps, of the window of codes accepted around the
+    /// current step: a code is accepted if it matches any step in
+    /// `[current - window, current + window]`. This tolerates clients whose clocks run slightly
+    /// behind or ahead. See [Totp::verify_code] for how this interacts with code reuse
+    /// protection.
     #[must_use]
     pub fn with_window(mut self, window: u16) -> Self {
         self.window = window;
@@ -120,9 +311,16 @@ impl Totp {
 
     /// Checks if provided code is valid.
     ///
-    /// For scratch code, used one will be removed.
+    /// An 8-character `code` is tried against the scratch list first (and removed on a match);
+    /// if it's not a known scratch code, it falls through to the time-based check below, so
+    /// 8-digit TOTP codes ([Totp::with_digits]`(8)`) keep working alongside scratch codes.
     ///
-    /// If code reusing is disabled, current time frome will be saved.
+    /// For a time-based code, any step within the skew [Totp::with_window] allows is tried, not
+    /// just past ones, so clients with a slightly fast clock are still accepted. If code reusing
+    /// is disabled, only a step strictly newer than the last accepted one is honored: the
+    /// highest matched step is saved as `last_step`, and a code whose best-matching step is not
+    /// newer than that is rejected with [VerifyError::CodeUsed], even if it falls inside the
+    /// skew window. This guarantees a code can never be replayed, regardless of clock skew.
     ///
     /// # Example
     ///
@@ -146,17 +344,13 @@ impl Totp {
     /// ```
     pub fn verify_code(&mut self, code: &str) -> Result<(), VerifyError> {
         if code.len() == 8 {
-            // check scratch code
+            // check scratch code, falling through to the time-based check on a miss so an
+            // 8-digit TOTP code isn't shadowed by scratch-code handling
             let l = self.scratch.len();
-            if l < 1 {
-                return Err(VerifyError::InvalidCode);
-            }
             self.scratch.retain(|v| v != code);
             if self.scratch.len() != l {
                 return Ok(());
             }
-
-            return Err(VerifyError::InvalidCode);
         }
 
         let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
@@ -165,33 +359,203 @@ impl Totp {
                 return Err(VerifyError::Time(err));
             }
         };
-        let step = now / 30;
+        let step = now / self.step;
+        let window = self.window as u64;
+        let low = step.saturating_sub(window);
+        let high = step.saturating_add(window);
 
-        if !self.reusable && self.last_step == step {
-            return Err(VerifyError::CodeUsed);
+        let mut matched: Option<u64> = None;
+        for s in low..=high {
+            if hotp(&self.secret, s, self.algorithm, self.digits).as_deref() == Some(code) {
+                matched = Some(matched.map_or(s, |best| best.max(s)));
+            }
         }
 
-        let ok = google_authenticator::verify_code!(&self.secret, code, self.window as u64, step);
-        if ok && !self.reusable {
-            self.last_step = step;
-        }
+        let s = match matched {
+            Some(s) => s,
+            None => return Err(VerifyError::InvalidCode),
+        };
 
-        match ok {
-            true => Ok(()),
-            _ => Err(VerifyError::InvalidCode),
+        if !self.reusable {
+            if s <= self.last_step {
+                return Err(VerifyError::CodeUsed);
+            }
+            self.last_step = s;
         }
+
+        Ok(())
     }
 
     /// Generates `otpauth://` uri. You may generate qrcode image with that.
     ///
-    /// The generated uri is `othauth://totp/name?secret=secret&issuer=issuer`.
+    /// The generated uri is
+    /// `otpauth://totp/name?secret=secret&issuer=issuer&algorithm=algorithm&digits=digits&period=period`.
     #[inline]
     #[must_use]
     pub fn uri(&self, name: &str, issuer: &str) -> String {
         format!(
-            "otpauth://totp/{}?secret={}&issuer={}",
-            name, &self.secret, issuer
+            "otpauth://totp/{}?secret={}&issuer={}&algorithm={}&digits={}&period={}",
+            name,
+            &self.secret,
+            issuer,
+            self.algorithm.as_str(),
+            self.digits,
+            self.step
+        )
+    }
+
+    /// Renders the [Totp::uri] enrollment uri as a QR code and returns it as a base64
+    /// `data:image/png` string, ready to drop into an `<img src="...">` tag.
+    ///
+    /// Requires the `qr` feature.
+    #[cfg(feature = "qr")]
+    pub fn qr_png_base64(&self, name: &str, issuer: &str) -> Result<String, QrError> {
+        let code = qrcode::QrCode::new(self.uri(name, issuer).as_bytes()).map_err(QrError::Qr)?;
+        let image = code.render::<image::Luma<u8>>().build();
+
+        let mut png = Vec::new();
+        image::DynamicImage::ImageLuma8(image)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .map_err(QrError::Image)?;
+
+        Ok(format!(
+            "data:image/png;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(png)
+        ))
+    }
+
+    /// Reconstructs a Totp from an `otpauth://totp/...` uri, as generated by [Totp::uri] (or
+    /// exported by another authenticator app).
+    ///
+    /// `algorithm`, `digits`, and `period` query parameters are honored when present and fall
+    /// back to the usual defaults (SHA1, 6 digits, 30 seconds) otherwise. A malformed or
+    /// out-of-range `digits` or `period` value is rejected rather than silently defaulted; an
+    /// unrecognised `algorithm` value is not rejected, it is ignored. The `secret` is subject to
+    /// the same minimum length as [Totp::with_secret] (80 bits / 10 bytes). The resulting Totp
+    /// has code reusing forbidden and no scratch codes, since scratch codes aren't part of the
+    /// `otpauth://` format.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use eztotp::Totp;
+    ///
+    /// let totp = Totp::new();
+    /// let uri = totp.uri("alice", "example.com");
+    /// let restored = Totp::from_uri(&uri).unwrap();
+    /// assert_eq!(totp.secret(), restored.secret());
+    /// ```
+    pub fn from_uri(uri: &str) -> Result<Totp, UriError> {
+        let rest = uri
+            .strip_prefix("otpauth://totp/")
+            .ok_or(UriError::BadScheme)?;
+        let query = match rest.split_once('?') {
+            Some((_label, query)) => query,
+            None => "",
+        };
+
+        let mut secret = None;
+        let mut algorithm = Algorithm::default();
+        let mut digits = default_digits();
+        let mut step = default_step();
+
+        for pair in query.split('&') {
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            let value = url_decode(value);
+            match key {
+                "secret" => secret = Some(value),
+                "algorithm" => {
+                    algorithm = match value.to_uppercase().as_str() {
+                        "SHA1" => Algorithm::Sha1,
+                        "SHA256" => Algorithm::Sha256,
+                        "SHA512" => Algorithm::Sha512,
+                        _ => algorithm,
+                    };
+                }
+                "digits" => {
+                    let parsed: u32 = value.parse().map_err(|_| UriError::InvalidDigits)?;
+                    if !(1..=MAX_DIGITS).contains(&parsed) {
+                        return Err(UriError::InvalidDigits);
+                    }
+                    digits = parsed;
+                }
+                "period" => {
+                    let parsed: u64 = value.parse().map_err(|_| UriError::InvalidPeriod)?;
+                    if parsed == 0 {
+                        return Err(UriError::InvalidPeriod);
+                    }
+                    step = parsed;
+                }
+                _ => {}
+            }
+        }
+
+        let secret = secret.ok_or(UriError::MissingSecret)?;
+        match decode_secret(&secret) {
+            Some(bytes) if bytes.len() >= MIN_SECRET_BYTES => {}
+            _ => return Err(UriError::InvalidSecret),
+        }
+
+        Ok(Totp {
+            secret,
+            scratch: vec![],
+            window: 1,
+            reusable: false,
+            last_step: 0,
+            algorithm,
+            digits,
+            step,
+        })
+    }
+
+    /// Computes the code expected at an arbitrary unix timestamp.
+    ///
+    /// Useful for server-side display, testing harnesses, and email/SMS fallback flows where
+    /// you need the current code without going through [Totp::verify_code].
+    ///
+    /// Returns [GenerateError::InvalidSecret] if the secret isn't valid base32. Every
+    /// constructor on this struct guarantees a valid secret, but a `Totp` obtained via
+    /// [serde::Deserialize] from untrusted data isn't checked, so this is still reported as an
+    /// error rather than assumed away.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use eztotp::Totp;
+    ///
+    /// let totp = Totp::new();
+    /// let code = totp.generate_at(1_700_000_000).unwrap();
+    /// assert_eq!(code.len(), 6);
+    /// ```
+    pub fn generate_at(&self, unix_secs: u64) -> Result<String, GenerateError> {
+        hotp(
+            &self.secret,
+            unix_secs / self.step,
+            self.algorithm,
+            self.digits,
         )
+        .ok_or(GenerateError::InvalidSecret)
+    }
+
+    /// Computes the code expected at the current time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use eztotp::Totp;
+    ///
+    /// let mut totp = Totp::new();
+    /// let code = totp.generate_current().unwrap();
+    /// assert!(totp.verify(&code));
+    /// ```
+    pub fn generate_current(&self) -> Result<String, GenerateError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(GenerateError::Time)?
+            .as_secs();
+        self.generate_at(now)
     }
 
     /// Get secret.
@@ -219,6 +583,75 @@ impl Default for Totp {
     }
 }
 
+/// Converts between raw secret bytes and their base32 representation, as stored by [Totp].
+///
+/// Complements [Totp::secret] and [Totp::with_secret] for users who need to move a secret to or
+/// from raw bytes rather than the base32 string form.
+pub struct Secret;
+
+impl Secret {
+    /// Encodes raw bytes as an RFC4648 base32 string without padding.
+    #[must_use]
+    pub fn to_base32(bytes: &[u8]) -> String {
+        base32::encode(base32::Alphabet::RFC4648 { padding: false }, bytes)
+    }
+
+    /// Decodes a base32 string into raw bytes.
+    pub fn from_base32(s: &str) -> Result<Vec<u8>, SecretError> {
+        decode_secret(s).ok_or(SecretError::InvalidBase32)
+    }
+}
+
+/// Errors reported by [Totp::with_secret] and [Secret::from_base32].
+#[derive(Debug)]
+pub enum SecretError {
+    /// The secret is not valid base32.
+    InvalidBase32,
+    /// The secret is valid base32 but decodes to fewer than 80 bits (10 bytes).
+    ///
+    /// Only returned by [Totp::with_secret].
+    InvalidLength,
+}
+
+/// Errors reported by [Totp::qr_png_base64].
+#[cfg(feature = "qr")]
+#[derive(Debug)]
+pub enum QrError {
+    /// Failed to encode the uri as a QR code.
+    Qr(qrcode::types::QrError),
+    /// Failed to encode the QR code as a PNG.
+    Image(image::ImageError),
+}
+
+/// Errors reported by [Totp::from_uri].
+#[derive(Debug)]
+pub enum UriError {
+    /// The uri is not an `otpauth://totp/...` uri.
+    BadScheme,
+    /// The uri has no `secret` query parameter.
+    MissingSecret,
+    /// The `secret` query parameter is not valid base32, or is shorter than
+    /// [Totp::with_secret] accepts.
+    InvalidSecret,
+    /// The `digits` query parameter is missing a value, isn't a number, or is out of the
+    /// `1..=9` range [Totp::with_digits] accepts.
+    InvalidDigits,
+    /// The `period` query parameter is missing a value, isn't a number, or is `0`.
+    InvalidPeriod,
+}
+
+/// Errors reported by [Totp::generate_at] and [Totp::generate_current].
+#[derive(Debug)]
+pub enum GenerateError {
+    /// Failed to get system time.
+    Time(SystemTimeError),
+    /// The secret is not valid base32.
+    ///
+    /// Every constructor on [Totp] guarantees a valid secret, so this can only happen for a
+    /// `Totp` obtained via [serde::Deserialize] from untrusted data.
+    InvalidSecret,
+}
+
 /// Errors reoprted by [Totp::verify_code].
 #[derive(Debug)]
 pub enum VerifyError {
@@ -245,3 +678,70 @@ impl PartialEq for VerifyError {
         self.as_u8() == other.as_u8()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eight_digit_code_is_verified_as_totp_not_scratch() {
+        let mut totp = Totp::new().with_digits(8);
+        let code = totp.generate_current().expect("system time available");
+        assert!(totp.verify(&code));
+    }
+
+    #[test]
+    fn eight_digit_totp_instance_still_redeems_scratch_codes() {
+        let mut totp = Totp::new().with_digits(8);
+        let scratch = totp.scratch_codes()[0].to_owned();
+        assert!(totp.verify(&scratch));
+        assert_eq!(totp.scratch_codes().len(), 7);
+    }
+
+    #[test]
+    fn with_secret_rejects_wrong_length() {
+        let err = Totp::with_secret("").unwrap_err();
+        assert!(matches!(err, SecretError::InvalidLength));
+    }
+
+    #[test]
+    fn with_step_clamps_zero_to_one() {
+        let totp = Totp::new().with_step(0);
+        totp.generate_at(0).expect("secret is valid base32");
+    }
+
+    #[test]
+    fn generate_at_reports_invalid_secret_instead_of_panicking() {
+        // Bypasses every constructor to simulate a Totp deserialized from untrusted data.
+        let totp = Totp {
+            secret: "not valid base32!".to_owned(),
+            scratch: vec![],
+            window: 1,
+            reusable: false,
+            last_step: 0,
+            algorithm: Algorithm::default(),
+            digits: default_digits(),
+            step: default_step(),
+        };
+        assert!(matches!(
+            totp.generate_at(0),
+            Err(GenerateError::InvalidSecret)
+        ));
+    }
+
+    #[test]
+    fn from_uri_rejects_malformed_digits_and_period() {
+        let secret = Totp::new().secret().to_owned();
+        let bad_digits = format!("otpauth://totp/x?secret={secret}&digits=abc");
+        assert!(matches!(
+            Totp::from_uri(&bad_digits),
+            Err(UriError::InvalidDigits)
+        ));
+
+        let zero_period = format!("otpauth://totp/x?secret={secret}&period=0");
+        assert!(matches!(
+            Totp::from_uri(&zero_period),
+            Err(UriError::InvalidPeriod)
+        ));
+    }
+}